@@ -1,16 +1,76 @@
 use crate::auth_verifier::AuthScope;
 use crate::common::get_random_str;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use jwt_simple::prelude::*;
 use secp256k1::Keypair;
 
+/// The signing algorithm advertised in a JWT's `alg` header. ATProto `did:key`
+/// material comes in two flavors — secp256k1 and NIST P-256 — plus EdDSA for
+/// service keys, so the key kind and the signature algorithm are tracked as a
+/// pair rather than assumed from context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwsAlgorithm {
+    ES256K,
+    ES256,
+    EdDSA,
+}
+
+impl JwsAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JwsAlgorithm::ES256K => "ES256K",
+            JwsAlgorithm::ES256 => "ES256",
+            JwsAlgorithm::EdDSA => "EdDSA",
+        }
+    }
+}
+
+impl std::str::FromStr for JwsAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(alg: &str) -> Result<Self> {
+        match alg {
+            "ES256K" => Ok(JwsAlgorithm::ES256K),
+            "ES256" => Ok(JwsAlgorithm::ES256),
+            "EdDSA" => Ok(JwsAlgorithm::EdDSA),
+            _ => Err(anyhow!("Unsupported JWS algorithm: {}", alg)),
+        }
+    }
+}
+
+/// The signing key behind a `CreateTokensOpts`, tagged with the `JwsAlgorithm`
+/// it signs with so callers never have to separately track key kind and alg.
+#[derive(Clone)]
+pub enum SigningKey {
+    Es256k(Keypair),
+    Es256(Vec<u8>),
+    Ed25519(Vec<u8>),
+}
+
+impl SigningKey {
+    pub fn alg(&self) -> JwsAlgorithm {
+        match self {
+            SigningKey::Es256k(_) => JwsAlgorithm::ES256K,
+            SigningKey::Es256(_) => JwsAlgorithm::ES256,
+            SigningKey::Ed25519(_) => JwsAlgorithm::EdDSA,
+        }
+    }
+}
+
+/// `jwt_key` is a `SigningKey` rather than a bare `secp256k1::Keypair`;
+/// code that builds `CreateTokensOpts` from a secp256k1 keypair passes
+/// `SigningKey::Es256k(keypair)`.
 pub struct CreateTokensOpts {
     pub did: String,
-    pub jwt_key: Keypair,
+    pub jwt_key: SigningKey,
     pub service_did: String,
     pub scope: Option<AuthScope>,
     pub jti: Option<String>,
     pub expires_in: Option<Duration>,
+    /// Lexicon method (e.g. `com.atproto.repo.createRecord`) this token is
+    /// scoped to. Set on minted service-auth tokens so the receiving service
+    /// can enforce `lxm` binding; `None` for ordinary session tokens.
+    pub lxm: Option<String>,
 }
 
 pub struct AuthToken {
@@ -29,6 +89,8 @@ pub struct RefreshToken {
 #[derive(Serialize, Deserialize)]
 pub struct CustomClaimObj {
     pub scope: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lxm: Option<String>,
 }
 
 pub fn create_tokens(opts: CreateTokensOpts) -> Result<(String, String)> {
@@ -39,14 +101,16 @@ pub fn create_tokens(opts: CreateTokensOpts) -> Result<(String, String)> {
         scope,
         jti,
         expires_in,
+        lxm,
     } = opts;
     let access_jwt = create_access_token(CreateTokensOpts {
         did: did.clone(),
-        jwt_key,
+        jwt_key: jwt_key.clone(),
         service_did: service_did.clone(),
         scope,
         expires_in,
         jti: None,
+        lxm,
     })?;
     let refresh_jwt = create_refresh_token(CreateTokensOpts {
         did,
@@ -55,6 +119,7 @@ pub fn create_tokens(opts: CreateTokensOpts) -> Result<(String, String)> {
         jti,
         expires_in,
         scope: None,
+        lxm: None,
     })?;
     Ok((access_jwt, refresh_jwt))
 }
@@ -66,22 +131,27 @@ pub fn create_access_token(opts: CreateTokensOpts) -> Result<String> {
         service_did,
         scope,
         expires_in,
+        lxm,
         ..
     } = opts;
     let scope = scope.unwrap_or_else(|| AuthScope::Access);
     let expires_in = expires_in.unwrap_or_else(|| Duration::from_hours(2));
-    let claims = Claims::with_custom_claims(
+    let is_service_token = lxm.is_some();
+    let mut claims = Claims::with_custom_claims(
         CustomClaimObj {
             scope: scope.as_str().to_owned(),
+            lxm,
         },
         expires_in,
     )
     .with_audience(service_did)
-    .with_subject(did);
-    // alg ES256K
-    let key = ES256kKeyPair::from_bytes(&*jwt_key.secret_bytes())?;
-    let token = key.sign(claims)?;
-    Ok(token)
+    .with_subject(did.clone());
+    // Service-auth tokens name the signer in `iss`, which the receiving
+    // service resolves to a signing key (see `verify_service_jwt`).
+    if is_service_token {
+        claims = claims.with_issuer(did);
+    }
+    sign_claims(&jwt_key, claims)
 }
 
 pub fn create_refresh_token(opts: CreateTokensOpts) -> Result<String> {
@@ -98,28 +168,64 @@ pub fn create_refresh_token(opts: CreateTokensOpts) -> Result<String> {
     let claims = Claims::with_custom_claims(
         CustomClaimObj {
             scope: AuthScope::Refresh.as_str().to_owned(),
+            lxm: None,
         },
         expires_in,
     )
     .with_audience(service_did)
     .with_subject(did)
     .with_jwt_id(jti);
-    // alg ES256K
-    let key = ES256kKeyPair::from_bytes(&*jwt_key.secret_bytes())?;
-    let token = key.sign(claims)?;
-    Ok(token)
+    sign_claims(&jwt_key, claims)
+}
+
+/// Signs with the `jwt_simple` keypair type matching `key`'s algorithm, so the
+/// JWT header's `alg` always reflects the key actually used.
+fn sign_claims(key: &SigningKey, claims: JWTClaims<CustomClaimObj>) -> Result<String> {
+    match key {
+        SigningKey::Es256k(keypair) => {
+            let key = ES256kKeyPair::from_bytes(&*keypair.secret_bytes())?;
+            Ok(key.sign(claims)?)
+        }
+        SigningKey::Es256(bytes) => {
+            let key = ES256KeyPair::from_bytes(bytes)?;
+            Ok(key.sign(claims)?)
+        }
+        SigningKey::Ed25519(bytes) => {
+            let key = Ed25519KeyPair::from_bytes(bytes)?;
+            Ok(key.sign(claims)?)
+        }
+    }
 }
 
 // @NOTE unsafe for verification, should only be used w/ direct output from createRefreshToken() or createTokens()
-pub fn decode_refresh_token(jwt: String, jwt_key: Keypair) -> Result<RefreshToken> {
-    let key = ES256kKeyPair::from_bytes(&*jwt_key.secret_bytes())?;
-    let public_key = key.public_key();
-    let claims = public_key.verify_token::<CustomClaimObj>(&jwt, None)?;
-    assert_eq!(claims.custom.scope, AuthScope::Refresh.as_str().to_owned(), "not a refresh token");
-    Ok(RefreshToken{
+pub fn decode_refresh_token(jwt: String, jwt_key: SigningKey) -> Result<RefreshToken> {
+    let claims: JWTClaims<CustomClaimObj> = match &jwt_key {
+        SigningKey::Es256k(keypair) => {
+            let key = ES256kKeyPair::from_bytes(&*keypair.secret_bytes())?;
+            key.public_key().verify_token::<CustomClaimObj>(&jwt, None)?
+        }
+        SigningKey::Es256(bytes) => {
+            let key = ES256KeyPair::from_bytes(bytes)?;
+            key.public_key().verify_token::<CustomClaimObj>(&jwt, None)?
+        }
+        SigningKey::Ed25519(bytes) => {
+            let key = Ed25519KeyPair::from_bytes(bytes)?;
+            key.public_key().verify_token::<CustomClaimObj>(&jwt, None)?
+        }
+    };
+    if claims.custom.scope != AuthScope::Refresh.as_str() {
+        return Err(anyhow!("not a refresh token"));
+    }
+    Ok(RefreshToken {
         scope: AuthScope::from_str(&claims.custom.scope)?,
-        sub: claims.subject.unwrap(),
-        exp: claims.expires_at.unwrap(),
-        jti: claims.jwt_id.unwrap()
+        sub: claims
+            .subject
+            .ok_or_else(|| anyhow!("refresh token is missing a subject"))?,
+        exp: claims
+            .expires_at
+            .ok_or_else(|| anyhow!("refresh token is missing an expiry"))?,
+        jti: claims
+            .jwt_id
+            .ok_or_else(|| anyhow!("refresh token is missing a jti"))?,
     })
 }
@@ -0,0 +1,272 @@
+use super::auth::JwsAlgorithm;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jwt_simple::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Errors from verifying an inter-service auth JWT. Replaces the old
+/// `assert_eq!`-and-panic behavior of `decode_refresh_token` with variants a
+/// caller can match on and turn into an XRPC error response.
+#[derive(Debug, Error)]
+pub enum ServiceAuthError {
+    #[error("malformed service auth token: {0}")]
+    Malformed(String),
+    #[error("could not resolve a signing key for did {0}")]
+    UnresolvableDid(String),
+    #[error("unsupported signing algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("invalid signature or audience")]
+    BadSignature,
+    #[error("token is scoped to lexicon method {bound:?}, not {requested}")]
+    LxmMismatch { bound: Option<String>, requested: String },
+}
+
+/// A signing key advertised by a DID document, as returned by the identity
+/// resolver for a given `did:key`/`did:plc`/`did:web` verification method.
+/// `alg` is the raw algorithm identifier the DID document advertises (e.g.
+/// `"ES256K"`) rather than a `JwsAlgorithm`, since a document can name an
+/// algorithm this PDS doesn't support verifying — `verify_service_jwt` parses
+/// it and reports `ServiceAuthError::UnsupportedAlgorithm` when it doesn't.
+#[derive(Clone)]
+pub struct DidSigningKey {
+    pub alg: String,
+    pub public_key_bytes: Vec<u8>,
+}
+
+/// Resolves a DID to the signing keys in its DID document. Implemented
+/// elsewhere against the crate's PLC/web identity resolver; kept as a trait
+/// here so verification can be unit-tested without a network resolver.
+pub trait DidKeyResolver {
+    fn resolve_signing_keys(&self, did: &str) -> anyhow::Result<Vec<DidSigningKey>>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct LxmClaims {
+    #[serde(default)]
+    lxm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UnverifiedPayload {
+    iss: String,
+}
+
+pub struct ServiceJwtPayload {
+    pub iss: String,
+    pub lxm: Option<String>,
+}
+
+/// Verifies an inter-service auth JWT: resolves `iss` (the caller's DID) to a
+/// signing key via `resolver`, verifies the signature with the algorithm the
+/// header advertises, and enforces `aud == own_did` plus the `lxm`
+/// (lexicon-method) claim binding the token to a single XRPC method.
+pub fn verify_service_jwt(
+    jwt: &str,
+    own_did: &str,
+    lxm: Option<&str>,
+    resolver: &dyn DidKeyResolver,
+) -> Result<ServiceJwtPayload, ServiceAuthError> {
+    let payload_b64 = jwt
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| ServiceAuthError::Malformed("expected a 3-part JWT".to_owned()))?;
+    let payload_bytes = URL_SAFE_NO_PAD
+        .decode(payload_b64)
+        .map_err(|_| ServiceAuthError::Malformed("invalid base64 payload".to_owned()))?;
+    let unverified: UnverifiedPayload = serde_json::from_slice(&payload_bytes)
+        .map_err(|_| ServiceAuthError::Malformed("invalid JWT payload".to_owned()))?;
+
+    let signing_keys = resolver
+        .resolve_signing_keys(&unverified.iss)
+        .map_err(|_| ServiceAuthError::UnresolvableDid(unverified.iss.clone()))?;
+
+    let mut options = VerificationOptions::default();
+    options.allowed_audiences = Some(HashSet::from_iter([own_did.to_owned()]));
+
+    let mut first_unsupported_alg: Option<String> = None;
+    for key in &signing_keys {
+        let alg: JwsAlgorithm = match key.alg.parse() {
+            Ok(alg) => alg,
+            Err(_) => {
+                first_unsupported_alg.get_or_insert_with(|| key.alg.clone());
+                continue;
+            }
+        };
+        let claims = match alg {
+            JwsAlgorithm::ES256K => ES256kPublicKey::from_bytes(&key.public_key_bytes)
+                .ok()
+                .and_then(|k| k.verify_token::<LxmClaims>(jwt, Some(options.clone())).ok()),
+            JwsAlgorithm::ES256 => ES256PublicKey::from_bytes(&key.public_key_bytes)
+                .ok()
+                .and_then(|k| k.verify_token::<LxmClaims>(jwt, Some(options.clone())).ok()),
+            JwsAlgorithm::EdDSA => Ed25519PublicKey::from_bytes(&key.public_key_bytes)
+                .ok()
+                .and_then(|k| k.verify_token::<LxmClaims>(jwt, Some(options.clone())).ok()),
+        };
+        if let Some(claims) = claims {
+            if let Some(requested) = lxm {
+                if claims.custom.lxm.as_deref() != Some(requested) {
+                    return Err(ServiceAuthError::LxmMismatch {
+                        bound: claims.custom.lxm,
+                        requested: requested.to_owned(),
+                    });
+                }
+            }
+            return Ok(ServiceJwtPayload {
+                iss: unverified.iss,
+                lxm: claims.custom.lxm,
+            });
+        }
+    }
+
+    if signing_keys.is_empty() {
+        return Err(ServiceAuthError::UnresolvableDid(unverified.iss));
+    }
+    // Only report an unsupported algorithm if none of the resolved keys could
+    // even be attempted — a key we could verify against (but whose signature
+    // didn't check out) should still surface as `BadSignature`.
+    if let Some(alg) = first_unsupported_alg {
+        if signing_keys.iter().all(|k| k.alg.parse::<JwsAlgorithm>().is_err()) {
+            return Err(ServiceAuthError::UnsupportedAlgorithm(alg));
+        }
+    }
+    Err(ServiceAuthError::BadSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const OWN_DID: &str = "did:web:pds.example.com";
+    const CALLER_DID: &str = "did:web:appview.example.com";
+
+    struct MockResolver {
+        keys: HashMap<String, Vec<DidSigningKey>>,
+    }
+
+    impl DidKeyResolver for MockResolver {
+        fn resolve_signing_keys(&self, did: &str) -> anyhow::Result<Vec<DidSigningKey>> {
+            Ok(self.keys.get(did).cloned().unwrap_or_default())
+        }
+    }
+
+    fn resolver_for(did: &str, keys: Vec<DidSigningKey>) -> MockResolver {
+        MockResolver {
+            keys: HashMap::from([(did.to_owned(), keys)]),
+        }
+    }
+
+    /// Signs a service-auth token the way `create_access_token` would, minus
+    /// the standard session claims this module doesn't care about, so tests
+    /// can exercise `verify_service_jwt` without going through the full
+    /// token-creation path.
+    fn sign_es256k(iss: &str, aud: &str, lxm: Option<&str>) -> (String, DidSigningKey) {
+        let keypair = ES256kKeyPair::generate();
+        let public_key_bytes = keypair.public_key().to_bytes();
+        let claims = Claims::with_custom_claims(
+            LxmClaims {
+                lxm: lxm.map(|l| l.to_owned()),
+            },
+            Duration::from_hours(1),
+        )
+        .with_issuer(iss)
+        .with_audience(aud);
+        let jwt = keypair.sign(claims).unwrap();
+        (
+            jwt,
+            DidSigningKey {
+                alg: JwsAlgorithm::ES256K.as_str().to_owned(),
+                public_key_bytes,
+            },
+        )
+    }
+
+    #[test]
+    fn verifies_a_valid_token_with_matching_audience_and_lxm() {
+        let (jwt, key) = sign_es256k(CALLER_DID, OWN_DID, Some("com.atproto.repo.createRecord"));
+        let resolver = resolver_for(CALLER_DID, vec![key]);
+
+        let payload =
+            verify_service_jwt(&jwt, OWN_DID, Some("com.atproto.repo.createRecord"), &resolver).unwrap();
+        assert_eq!(payload.iss, CALLER_DID);
+        assert_eq!(payload.lxm.as_deref(), Some("com.atproto.repo.createRecord"));
+    }
+
+    #[test]
+    fn rejects_a_token_with_the_wrong_audience() {
+        let (jwt, key) = sign_es256k(CALLER_DID, "did:web:someone-else.example.com", None);
+        let resolver = resolver_for(CALLER_DID, vec![key]);
+
+        let err = verify_service_jwt(&jwt, OWN_DID, None, &resolver).unwrap_err();
+        assert!(matches!(err, ServiceAuthError::BadSignature));
+    }
+
+    #[test]
+    fn rejects_a_lxm_mismatch() {
+        let (jwt, key) = sign_es256k(CALLER_DID, OWN_DID, Some("com.atproto.repo.createRecord"));
+        let resolver = resolver_for(CALLER_DID, vec![key]);
+
+        let err = verify_service_jwt(&jwt, OWN_DID, Some("com.atproto.repo.deleteRecord"), &resolver)
+            .unwrap_err();
+        assert!(matches!(err, ServiceAuthError::LxmMismatch { .. }));
+    }
+
+    #[test]
+    fn rejects_a_missing_lxm_when_the_endpoint_requires_one() {
+        let (jwt, key) = sign_es256k(CALLER_DID, OWN_DID, None);
+        let resolver = resolver_for(CALLER_DID, vec![key]);
+
+        let err = verify_service_jwt(&jwt, OWN_DID, Some("com.atproto.repo.createRecord"), &resolver)
+            .unwrap_err();
+        match err {
+            ServiceAuthError::LxmMismatch { bound, requested } => {
+                assert_eq!(bound, None);
+                assert_eq!(requested, "com.atproto.repo.createRecord");
+            }
+            other => panic!("expected LxmMismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_an_unresolvable_did() {
+        let (jwt, _key) = sign_es256k(CALLER_DID, OWN_DID, None);
+        let resolver = resolver_for("did:web:someone-else.example.com", vec![]);
+
+        let err = verify_service_jwt(&jwt, OWN_DID, None, &resolver).unwrap_err();
+        assert!(matches!(err, ServiceAuthError::UnresolvableDid(did) if did == CALLER_DID));
+    }
+
+    #[test]
+    fn rejects_a_did_document_key_with_an_unsupported_algorithm() {
+        let (jwt, _key) = sign_es256k(CALLER_DID, OWN_DID, None);
+        let resolver = resolver_for(
+            CALLER_DID,
+            vec![DidSigningKey {
+                alg: "RS256".to_owned(),
+                public_key_bytes: vec![0u8; 32],
+            }],
+        );
+
+        let err = verify_service_jwt(&jwt, OWN_DID, None, &resolver).unwrap_err();
+        assert!(matches!(err, ServiceAuthError::UnsupportedAlgorithm(alg) if alg == "RS256"));
+    }
+
+    #[test]
+    fn rejects_a_tampered_signature() {
+        let (jwt, key) = sign_es256k(CALLER_DID, OWN_DID, None);
+        let resolver = resolver_for(CALLER_DID, vec![key]);
+
+        // Flip a byte in the signature segment; the payload still parses (we
+        // need `iss` to resolve a key) but the signature no longer verifies.
+        let parts: Vec<&str> = jwt.split('.').collect();
+        let mut sig_bytes = URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+        let last = sig_bytes.len() - 1;
+        sig_bytes[last] ^= 0xff;
+        let tampered_jwt = format!("{}.{}.{}", parts[0], parts[1], URL_SAFE_NO_PAD.encode(sig_bytes));
+
+        let err = verify_service_jwt(&tampered_jwt, OWN_DID, None, &resolver).unwrap_err();
+        assert!(matches!(err, ServiceAuthError::BadSignature));
+    }
+}
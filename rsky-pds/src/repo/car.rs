@@ -0,0 +1,548 @@
+use super::mst::{NodeEntry, MST};
+use crate::storage::BlockStore;
+use anyhow::{anyhow, Result};
+use libipld::cid::Cid;
+use libipld::multihash::{Code, MultihashDigest};
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read};
+
+/// The DAG-CBOR tag that marks a byte string as a CID (tag 42), and the
+/// leading `0x00` "multibase-less identity" byte that precedes the CID's
+/// own bytes inside that tagged byte string — both fixed by the DAG-CBOR
+/// spec so a CID round-trips losslessly through the IPLD data model.
+const CID_TAG: u64 = 42;
+
+const DAG_CBOR_CODEC: u64 = 0x71;
+
+/// CARv1 headers are a handful of CIDs at most; reject anything claiming to
+/// be bigger than this rather than trusting an attacker-controlled length
+/// prefix.
+const MAX_HEADER_SIZE: u64 = 64 * 1024;
+/// Repo blocks (MST nodes, records) are expected to stay well under this;
+/// reject outsized length prefixes before allocating a buffer for them.
+const MAX_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+struct CarHeader {
+    version: u64,
+    roots: Vec<Cid>,
+}
+
+/// Walks an MST's reachable blocks (mirroring the node structure that
+/// `serialize_node_data`/`NodeEntry` already understand) and streams every
+/// block into a CAR v1 byte stream: a varint-length-prefixed dag-cbor
+/// header carrying the root CID, followed by `len || cid || block-bytes`
+/// records for every block. This is the block-walking + packaging
+/// `com.atproto.sync.getRepo` and full-repo migration need.
+///
+/// `root` is the signed *commit* CID, which is distinct from `mst.pointer`
+/// (the commit's `data` field, i.e. the MST's own top node) — a repo's
+/// commit object is itself opaque to the MST walk, so its bytes are written
+/// as-is from `storage` rather than treated as a tree node.
+pub fn write_car<S: BlockStore + Clone>(root: Cid, mst: &MST<S>) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_header(&mut out, root)?;
+
+    let mut seen = BTreeSet::new();
+    seen.insert(root);
+    let root_bytes = mst.storage.get_bytes(&root)?;
+    write_block(&mut out, &root, &root_bytes);
+
+    if seen.insert(mst.pointer) {
+        let mst_bytes = mst.storage.get_bytes(&mst.pointer)?;
+        write_block(&mut out, &mst.pointer, &mst_bytes);
+    }
+
+    walk_blocks(mst, &mut seen, &mut out)?;
+    Ok(out)
+}
+
+fn walk_blocks<S: BlockStore + Clone>(
+    mst: &MST<S>,
+    seen: &mut BTreeSet<Cid>,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    for entry in mst.get_entries()? {
+        match entry {
+            NodeEntry::MST(child) => {
+                if seen.insert(child.pointer) {
+                    let bytes = child.storage.get_bytes(&child.pointer)?;
+                    write_block(out, &child.pointer, &bytes);
+                    walk_blocks(&child, seen, out)?;
+                }
+            }
+            NodeEntry::Leaf(leaf) => {
+                if seen.insert(leaf.value) {
+                    let bytes = mst.storage.get_bytes(&leaf.value)?;
+                    write_block(out, &leaf.value, &bytes);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes the CARv1 header as DAG-CBOR so go-car/js `@ipld/car` can parse
+/// it, not just this module round-tripping against itself: the root is a
+/// tag-42 CID-in-DAG-CBOR byte string rather than a bare CBOR byte string,
+/// and the map's two keys are written in canonical DAG-CBOR order (shortest
+/// key first, so `roots` precedes `version`) instead of struct declaration
+/// order.
+fn write_header(out: &mut Vec<u8>, root: Cid) -> Result<()> {
+    let header = CarHeader {
+        version: 1,
+        roots: vec![root],
+    };
+    let mut body = Vec::new();
+    write_map_head(&mut body, 2);
+    write_text_string(&mut body, "roots");
+    write_array_head(&mut body, header.roots.len() as u64);
+    for cid in &header.roots {
+        write_cid(&mut body, cid);
+    }
+    write_text_string(&mut body, "version");
+    write_uint(&mut body, header.version);
+
+    write_varint(out, body.len() as u64);
+    out.extend_from_slice(&body);
+    Ok(())
+}
+
+fn write_block(out: &mut Vec<u8>, cid: &Cid, bytes: &[u8]) {
+    let cid_bytes = cid.to_bytes();
+    write_varint(out, (cid_bytes.len() + bytes.len()) as u64);
+    out.extend_from_slice(&cid_bytes);
+    out.extend_from_slice(bytes);
+}
+
+/// Writes a CBOR major-type/argument head using the shortest encoding for
+/// `arg`, per the deterministic CBOR rules DAG-CBOR requires.
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let top = major << 5;
+    if arg < 24 {
+        out.push(top | arg as u8);
+    } else if arg <= u8::MAX as u64 {
+        out.push(top | 24);
+        out.push(arg as u8);
+    } else if arg <= u16::MAX as u64 {
+        out.push(top | 25);
+        out.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= u32::MAX as u64 {
+        out.push(top | 26);
+        out.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(top | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+fn write_map_head(out: &mut Vec<u8>, len: u64) {
+    write_head(out, 5, len);
+}
+
+fn write_array_head(out: &mut Vec<u8>, len: u64) {
+    write_head(out, 4, len);
+}
+
+fn write_uint(out: &mut Vec<u8>, value: u64) {
+    write_head(out, 0, value);
+}
+
+fn write_text_string(out: &mut Vec<u8>, s: &str) {
+    write_head(out, 3, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_cid(out: &mut Vec<u8>, cid: &Cid) {
+    write_head(out, 6, CID_TAG);
+    let cid_bytes = cid.to_bytes();
+    write_head(out, 2, cid_bytes.len() as u64 + 1);
+    out.push(0x00);
+    out.extend_from_slice(&cid_bytes);
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn read_varint(cursor: &mut Cursor<&[u8]>) -> Result<u64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0u8; 1];
+        cursor.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    Ok(value)
+}
+
+/// Reads a CBOR major-type/argument head, mirroring `write_head`'s encoding.
+fn read_head(cursor: &mut Cursor<&[u8]>) -> Result<(u8, u64)> {
+    let mut first = [0u8; 1];
+    cursor.read_exact(&mut first)?;
+    let major = first[0] >> 5;
+    let low = first[0] & 0x1f;
+    let arg = match low {
+        0..=23 => low as u64,
+        24 => {
+            let mut b = [0u8; 1];
+            cursor.read_exact(&mut b)?;
+            b[0] as u64
+        }
+        25 => {
+            let mut b = [0u8; 2];
+            cursor.read_exact(&mut b)?;
+            u16::from_be_bytes(b) as u64
+        }
+        26 => {
+            let mut b = [0u8; 4];
+            cursor.read_exact(&mut b)?;
+            u32::from_be_bytes(b) as u64
+        }
+        27 => {
+            let mut b = [0u8; 8];
+            cursor.read_exact(&mut b)?;
+            u64::from_be_bytes(b)
+        }
+        _ => return Err(anyhow!("Unsupported CBOR length encoding: {}", low)),
+    };
+    Ok((major, arg))
+}
+
+fn read_text_string(cursor: &mut Cursor<&[u8]>) -> Result<String> {
+    let (major, len) = read_head(cursor)?;
+    if major != 3 {
+        return Err(anyhow!("Expected a CBOR text string, got major type {}", major));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    cursor.read_exact(&mut bytes)?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn read_cid(cursor: &mut Cursor<&[u8]>) -> Result<Cid> {
+    let (major, tag) = read_head(cursor)?;
+    if major != 6 || tag != CID_TAG {
+        return Err(anyhow!("Expected a tag-{} CID, got major {} tag {}", CID_TAG, major, tag));
+    }
+    let (byte_major, len) = read_head(cursor)?;
+    if byte_major != 2 {
+        return Err(anyhow!("Expected a CBOR byte string under the CID tag"));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    cursor.read_exact(&mut bytes)?;
+    if bytes.first() != Some(&0x00) {
+        return Err(anyhow!("CID byte string missing the leading identity-multibase byte"));
+    }
+    Ok(Cid::read_bytes(Cursor::new(&bytes[1..]))?)
+}
+
+/// Parses the DAG-CBOR header `write_header` produces, tolerating either
+/// key order so headers from other CAR writers (which may not sort keys
+/// the same way) still parse.
+fn read_header(header_bytes: &[u8]) -> Result<CarHeader> {
+    let mut cursor = Cursor::new(header_bytes);
+    let (major, len) = read_head(&mut cursor)?;
+    if major != 5 {
+        return Err(anyhow!("CAR header is not a DAG-CBOR map"));
+    }
+    let mut version = None;
+    let mut roots = None;
+    for _ in 0..len {
+        match read_text_string(&mut cursor)?.as_str() {
+            "version" => {
+                let (value_major, value) = read_head(&mut cursor)?;
+                if value_major != 0 {
+                    return Err(anyhow!("CAR header `version` is not a uint"));
+                }
+                version = Some(value);
+            }
+            "roots" => {
+                let (array_major, count) = read_head(&mut cursor)?;
+                if array_major != 4 {
+                    return Err(anyhow!("CAR header `roots` is not an array"));
+                }
+                let mut parsed = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    parsed.push(read_cid(&mut cursor)?);
+                }
+                roots = Some(parsed);
+            }
+            other => return Err(anyhow!("Unexpected CAR header key: {}", other)),
+        }
+    }
+    Ok(CarHeader {
+        version: version.ok_or_else(|| anyhow!("CAR header missing `version`"))?,
+        roots: roots.ok_or_else(|| anyhow!("CAR header missing `roots`"))?,
+    })
+}
+
+/// Ingests a CAR v1 stream, validating every block's CID against its
+/// recomputed hash, loading the blocks into `storage`, and reconstructing
+/// the repo MST through `MST::load` (which walks the tree via
+/// `deserialize_node_data` on demand) so the result is a tree ready to
+/// query exactly like one loaded from any other `BlockStore`.
+///
+/// The CAR root is the signed *commit* object, not an MST node — its
+/// DAG-CBOR `data` field is the CID of the actual tree root — so this reads
+/// the ingested root block back out and pulls `data` out of it via
+/// `read_commit_data_pointer` before calling `MST::load`.
+pub fn read_car<S: BlockStore + Clone>(data: &[u8], storage: S) -> Result<MST<S>> {
+    let mut cursor = Cursor::new(data);
+    let header_len = read_varint(&mut cursor)?;
+    if header_len > MAX_HEADER_SIZE {
+        return Err(anyhow!(
+            "CAR header length {} exceeds the {} byte limit",
+            header_len,
+            MAX_HEADER_SIZE
+        ));
+    }
+    let mut header_bytes = vec![0u8; header_len as usize];
+    cursor.read_exact(&mut header_bytes)?;
+    let header = read_header(&header_bytes)?;
+    let root = *header
+        .roots
+        .get(0)
+        .ok_or_else(|| anyhow!("CAR file has no root"))?;
+    if header.version != 1 {
+        return Err(anyhow!("Unsupported CAR version: {}", header.version));
+    }
+
+    while (cursor.position() as usize) < data.len() {
+        let entry_len = read_varint(&mut cursor)?;
+        if entry_len > MAX_BLOCK_SIZE {
+            return Err(anyhow!(
+                "CAR block length {} exceeds the {} byte limit",
+                entry_len,
+                MAX_BLOCK_SIZE
+            ));
+        }
+        let entry_len = entry_len as usize;
+        let mut entry = vec![0u8; entry_len];
+        cursor.read_exact(&mut entry)?;
+        let cid = Cid::read_bytes(Cursor::new(&entry[..]))?;
+        let cid_len = cid.to_bytes().len();
+        let bytes = entry[cid_len..].to_vec();
+
+        let computed = cid_for_block_bytes(&bytes)?;
+        if computed != cid {
+            return Err(anyhow!(
+                "CAR block failed integrity check: expected {}, computed {}",
+                cid,
+                computed
+            ));
+        }
+        storage.put_block(cid, bytes)?;
+    }
+    let commit_bytes = storage.get_bytes(&root)?;
+    let data_pointer = read_commit_data_pointer(&commit_bytes)?;
+    MST::load(storage, data_pointer, None)
+}
+
+fn cid_for_block_bytes(bytes: &[u8]) -> Result<Cid> {
+    let hash = Code::Sha2_256.digest(bytes);
+    Ok(Cid::new_v1(DAG_CBOR_CODEC, hash))
+}
+
+/// Parses just enough of a repo commit (the DAG-CBOR map
+/// `{did, version, data, rev, prev, sig}` that a CAR's root block holds) to
+/// pull out the `data` field — the CID of the MST root node. Other keys are
+/// skipped generically via `skip_cbor_value` rather than modeled, so this
+/// doesn't need to track the full commit schema to find the one field the
+/// MST loader needs.
+fn read_commit_data_pointer(bytes: &[u8]) -> Result<Cid> {
+    let mut cursor = Cursor::new(bytes);
+    let (major, len) = read_head(&mut cursor)?;
+    if major != 5 {
+        return Err(anyhow!("CAR root block is not a DAG-CBOR map"));
+    }
+    let mut data = None;
+    for _ in 0..len {
+        let key = read_text_string(&mut cursor)?;
+        if key == "data" {
+            data = Some(read_cid(&mut cursor)?);
+        } else {
+            skip_cbor_value(&mut cursor)?;
+        }
+    }
+    data.ok_or_else(|| anyhow!("commit block is missing its `data` pointer"))
+}
+
+/// Skips over one DAG-CBOR value without decoding it, so
+/// `read_commit_data_pointer` can ignore commit fields it doesn't need.
+fn skip_cbor_value(cursor: &mut Cursor<&[u8]>) -> Result<()> {
+    let (major, arg) = read_head(cursor)?;
+    match major {
+        0 | 1 | 7 => {}
+        2 | 3 => {
+            let mut buf = vec![0u8; arg as usize];
+            cursor.read_exact(&mut buf)?;
+        }
+        4 => {
+            for _ in 0..arg {
+                skip_cbor_value(cursor)?;
+            }
+        }
+        5 => {
+            for _ in 0..arg {
+                skip_cbor_value(cursor)?; // key
+                skip_cbor_value(cursor)?; // value
+            }
+        }
+        6 => skip_cbor_value(cursor)?,
+        other => return Err(anyhow!("Unsupported CBOR major type while skipping: {}", other)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::mst::{cid_for_entries, serialize_node_data, Leaf};
+    use crate::storage::MemoryBlockStore;
+
+    fn put_leaf(storage: &MemoryBlockStore, key: &str, value_bytes: &[u8]) -> Leaf {
+        let value = cid_for_block_bytes(value_bytes).unwrap();
+        storage.put_block(value, value_bytes.to_vec()).unwrap();
+        Leaf {
+            key: key.to_owned(),
+            value,
+        }
+    }
+
+    fn build_single_node_tree(storage: &MemoryBlockStore) -> (Cid, MST<MemoryBlockStore>) {
+        let entries = vec![
+            NodeEntry::<MemoryBlockStore>::Leaf(put_leaf(storage, "com.example.record/aaa", b"one")),
+            NodeEntry::<MemoryBlockStore>::Leaf(put_leaf(storage, "com.example.record/bbb", b"two")),
+        ];
+        let data_root = cid_for_entries(entries.clone()).unwrap();
+        let data = serialize_node_data(entries).unwrap();
+        storage
+            .put_block(data_root, serde_cbor::to_vec(&data).unwrap())
+            .unwrap();
+        let mst = MST::load(storage.clone(), data_root, None).unwrap();
+        (data_root, mst)
+    }
+
+    /// Stores a minimal commit object — just the `data` field `read_car`
+    /// needs — and returns its CID, standing in for the real signed commit a
+    /// CAR's root block holds.
+    fn put_commit(storage: &MemoryBlockStore, data: Cid) -> Cid {
+        let mut body = Vec::new();
+        write_map_head(&mut body, 1);
+        write_text_string(&mut body, "data");
+        write_cid(&mut body, &data);
+        let cid = cid_for_block_bytes(&body).unwrap();
+        storage.put_block(cid, body).unwrap();
+        cid
+    }
+
+    #[test]
+    fn round_trips_a_tree_through_write_and_read_car() {
+        let storage = MemoryBlockStore::new();
+        let (data_root, mst) = build_single_node_tree(&storage);
+        let commit = put_commit(&storage, data_root);
+        let car_bytes = write_car(commit, &mst).unwrap();
+
+        let target = MemoryBlockStore::new();
+        let reconstructed = read_car(&car_bytes, target).unwrap();
+
+        let original_entries = mst.get_entries().unwrap();
+        let reconstructed_entries = reconstructed.get_entries().unwrap();
+        assert_eq!(original_entries.len(), reconstructed_entries.len());
+        for (original, reconstructed) in original_entries.iter().zip(reconstructed_entries.iter()) {
+            match (original, reconstructed) {
+                (NodeEntry::Leaf(a), NodeEntry::Leaf(b)) => {
+                    assert_eq!(a.key, b.key);
+                    assert_eq!(a.value, b.value);
+                }
+                _ => panic!("expected leaf entries"),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_a_corrupted_block() {
+        let storage = MemoryBlockStore::new();
+        let (data_root, mst) = build_single_node_tree(&storage);
+        let commit = put_commit(&storage, data_root);
+        let mut car_bytes = write_car(commit, &mst).unwrap();
+
+        // Flip a byte near the end of the stream, inside the last block's bytes.
+        let last = car_bytes.len() - 1;
+        car_bytes[last] ^= 0xff;
+
+        let target = MemoryBlockStore::new();
+        let err = read_car(&car_bytes, target).unwrap_err();
+        assert!(err.to_string().contains("integrity check"));
+    }
+
+    #[test]
+    fn rejects_a_root_block_that_is_not_a_commit() {
+        let storage = MemoryBlockStore::new();
+        let (data_root, mst) = build_single_node_tree(&storage);
+        // Use the MST's own data node as the CAR root, as if a caller
+        // confused the tree root with the commit that should wrap it.
+        let car_bytes = write_car(data_root, &mst).unwrap();
+
+        let target = MemoryBlockStore::new();
+        let err = read_car(&car_bytes, target).unwrap_err();
+        assert!(err.to_string().contains("`data` pointer"));
+    }
+
+    #[test]
+    fn rejects_an_oversized_header_length_prefix() {
+        let mut car_bytes = Vec::new();
+        write_varint(&mut car_bytes, MAX_HEADER_SIZE + 1);
+
+        let target = MemoryBlockStore::new();
+        let err = read_car(&car_bytes, target).unwrap_err();
+        assert!(err.to_string().contains("exceeds the"));
+    }
+
+    #[test]
+    fn rejects_an_oversized_block_length_prefix() {
+        let storage = MemoryBlockStore::new();
+        let (root, mst) = build_single_node_tree(&storage);
+        let mut car_bytes = write_car(root, &mst).unwrap();
+        write_varint(&mut car_bytes, MAX_BLOCK_SIZE + 1);
+
+        let target = MemoryBlockStore::new();
+        let err = read_car(&car_bytes, target).unwrap_err();
+        assert!(err.to_string().contains("exceeds the"));
+    }
+
+    #[test]
+    fn header_round_trips_with_canonical_key_order() {
+        let mut out = Vec::new();
+        let root = cid_for_block_bytes(b"root").unwrap();
+        write_header(&mut out, root).unwrap();
+
+        let header_len = {
+            let mut cursor = Cursor::new(&out[..]);
+            read_varint(&mut cursor).unwrap()
+        } as usize;
+        // `roots` (5 bytes) sorts before `version` (7 bytes) in canonical
+        // DAG-CBOR, so the map's first key must be `roots`.
+        let body = &out[out.len() - header_len..];
+        let mut cursor = Cursor::new(body);
+        read_head(&mut cursor).unwrap(); // map head
+        assert_eq!(read_text_string(&mut cursor).unwrap(), "roots");
+
+        let parsed = read_header(body).unwrap();
+        assert_eq!(parsed.version, 1);
+        assert_eq!(parsed.roots, vec![root]);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod car;
+pub mod mst;
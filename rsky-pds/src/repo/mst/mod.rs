@@ -0,0 +1,103 @@
+pub mod util;
+
+pub use util::*;
+
+use crate::common::ipld;
+use crate::storage::BlockStore;
+use anyhow::{anyhow, Result};
+use libipld::Cid;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::cell::RefCell;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Leaf {
+    pub key: String,
+    pub value: Cid,
+}
+
+#[derive(Clone)]
+pub enum NodeEntry<S: BlockStore + Clone> {
+    MST(MST<S>),
+    Leaf(Leaf),
+}
+
+impl<S: BlockStore + Clone> NodeEntry<S> {
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, NodeEntry::Leaf(_))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TreeEntry {
+    pub p: u8,
+    pub k: ByteBuf,
+    pub v: Cid,
+    pub t: Option<Cid>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeData {
+    pub l: Option<Cid>,
+    pub e: Vec<TreeEntry>,
+}
+
+/// A node of the repo Merkle Search Tree, lazily hydrated from whatever
+/// `BlockStore` it was loaded against. Generic over `S` so the same tree
+/// logic runs over SQL-backed storage in production or an in-memory store
+/// in tests, without the MST ever hard-coding a concrete backend.
+#[derive(Clone)]
+pub struct MST<S: BlockStore + Clone> {
+    pub storage: S,
+    pub pointer: Cid,
+    pub layer: Option<u32>,
+    /// Whether `get_entries` recomputes this node's block hash and checks it
+    /// against `pointer` before trusting the bytes storage returned. Trusted
+    /// fast paths (re-reading a block this process just wrote) can disable
+    /// this; anything crossing a trust boundary (sync, CAR import) should not.
+    pub verify_blocks: bool,
+    entries: RefCell<Option<Vec<NodeEntry<S>>>>,
+}
+
+impl<S: BlockStore + Clone> MST<S> {
+    pub fn load(storage: S, pointer: Cid, layer: Option<u32>) -> Result<Self> {
+        Self::load_with_options(storage, pointer, layer, true)
+    }
+
+    pub fn load_with_options(
+        storage: S,
+        pointer: Cid,
+        layer: Option<u32>,
+        verify_blocks: bool,
+    ) -> Result<Self> {
+        Ok(MST {
+            storage,
+            pointer,
+            layer,
+            verify_blocks,
+            entries: RefCell::new(None),
+        })
+    }
+
+    pub fn get_entries(&self) -> Result<Vec<NodeEntry<S>>> {
+        if let Some(entries) = self.entries.borrow().as_ref() {
+            return Ok(entries.clone());
+        }
+        let data = self.storage.get_bytes(&self.pointer)?;
+        let node_data: NodeData = serde_cbor::from_slice(&data)?;
+        if self.verify_blocks {
+            let computed = ipld::cid_for_cbor(&node_data)?;
+            if computed != self.pointer {
+                return Err(anyhow!(
+                    "Block hash mismatch: expected {}, computed {}",
+                    self.pointer,
+                    computed
+                ));
+            }
+        }
+        let entries =
+            deserialize_node_data_with_options(&self.storage, node_data, self.layer, self.verify_blocks)?;
+        *self.entries.borrow_mut() = Some(entries.clone());
+        Ok(entries)
+    }
+}
@@ -1,6 +1,6 @@
 use super::{Leaf, NodeData, NodeEntry, TreeEntry, MST};
 use crate::common::ipld;
-use crate::storage::SqlRepoReader;
+use crate::storage::BlockStore;
 use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
 use libipld::Cid;
@@ -42,7 +42,7 @@ pub fn ensure_valid_mst_key(key: &String) -> Result<()> {
     }
 }
 
-pub fn cid_for_entries(entries: Vec<NodeEntry>) -> Result<Cid> {
+pub fn cid_for_entries<S: BlockStore + Clone>(entries: Vec<NodeEntry<S>>) -> Result<Cid> {
     let data = serialize_node_data(entries)?;
     ipld::cid_for_cbor(&data)
 }
@@ -58,7 +58,7 @@ pub fn count_prefix_len(a: String, b: String) -> Result<usize> {
     Ok(x)
 }
 
-pub fn serialize_node_data(entries: Vec<NodeEntry>) -> Result<NodeData> {
+pub fn serialize_node_data<S: BlockStore + Clone>(entries: Vec<NodeEntry<S>>) -> Result<NodeData> {
     let mut data = NodeData {
         l: None,
         e: Vec::new(),
@@ -89,7 +89,7 @@ pub fn serialize_node_data(entries: Vec<NodeEntry>) -> Result<NodeData> {
             let prefix_len = count_prefix_len(last_key.to_owned(), l.key.to_owned())?;
             data.e.push(TreeEntry {
                 p: u8::try_from(prefix_len)?,
-                k: l.key[0..prefix_len].to_owned().into_bytes(),
+                k: l.key[0..prefix_len].to_owned().into_bytes().into(),
                 v: l.value,
                 t: subtree,
             });
@@ -99,12 +99,21 @@ pub fn serialize_node_data(entries: Vec<NodeEntry>) -> Result<NodeData> {
     Ok(data)
 }
 
-pub fn deserialize_node_data(
-    storage: &SqlRepoReader,
+pub fn deserialize_node_data<S: BlockStore + Clone>(
+    storage: &S,
     data: NodeData,
     layer: Option<u32>,
-) -> Result<Vec<NodeEntry>> {
-    let mut entries: Vec<NodeEntry> = Vec::new();
+) -> Result<Vec<NodeEntry<S>>> {
+    deserialize_node_data_with_options(storage, data, layer, true)
+}
+
+pub fn deserialize_node_data_with_options<S: BlockStore + Clone>(
+    storage: &S,
+    data: NodeData,
+    layer: Option<u32>,
+    verify_blocks: bool,
+) -> Result<Vec<NodeEntry<S>>> {
+    let mut entries: Vec<NodeEntry<S>> = Vec::new();
     if let Some(l) = data.l {
         let new_layer: Option<u32>;
         if let Some(layer) = layer {
@@ -112,7 +121,7 @@ pub fn deserialize_node_data(
         } else {
             new_layer = None;
         }
-        let mst = MST::load(storage.clone(), l, new_layer)?;
+        let mst = MST::load_with_options(storage.clone(), l, new_layer, verify_blocks)?;
         let mst = NodeEntry::MST(mst);
         entries.push(mst)
     }
@@ -134,7 +143,7 @@ pub fn deserialize_node_data(
             } else {
                 new_layer = None;
             }
-            let mst = MST::load(storage.clone(), t, new_layer)?;
+            let mst = MST::load_with_options(storage.clone(), t, new_layer, verify_blocks)?;
             let mst = NodeEntry::MST(mst);
             entries.push(mst)
         }
@@ -142,7 +151,7 @@ pub fn deserialize_node_data(
     Ok(entries)
 }
 
-pub fn layer_for_entries(entries: Vec<NodeEntry>) -> Result<Option<u32>> {
+pub fn layer_for_entries<S: BlockStore + Clone>(entries: Vec<NodeEntry<S>>) -> Result<Option<u32>> {
     let first_leaf = entries.into_iter().find(|entry| entry.is_leaf());
     if let Some(f) = first_leaf {
         match f {
@@ -175,4 +184,100 @@ pub fn leading_zeros_on_hash(key: &Vec<u8>) -> Result<u32> {
         }
     }
     Ok(leading_zeros)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryBlockStore;
+
+    fn cid_for(bytes: &[u8]) -> Cid {
+        ipld::cid_for_cbor(&serde_cbor::Value::Bytes(bytes.to_vec())).unwrap()
+    }
+
+    fn round_trip_through<S: BlockStore + Clone>(storage: S) {
+        let leaves = vec![
+            NodeEntry::<S>::Leaf(Leaf {
+                key: "com.example.record/aaa".to_owned(),
+                value: cid_for(b"one"),
+            }),
+            NodeEntry::<S>::Leaf(Leaf {
+                key: "com.example.record/bbb".to_owned(),
+                value: cid_for(b"two"),
+            }),
+        ];
+        let data = serialize_node_data(leaves.clone()).unwrap();
+        let bytes = serde_cbor::to_vec(&data).unwrap();
+        let cid = ipld::cid_for_cbor(&data).unwrap();
+        storage.put_block(cid, bytes.clone()).unwrap();
+
+        let reloaded: NodeData = serde_cbor::from_slice(&storage.get_bytes(&cid).unwrap()).unwrap();
+        let round_tripped = deserialize_node_data(&storage, reloaded, None).unwrap();
+
+        assert_eq!(round_tripped.len(), leaves.len());
+        for (original, reloaded) in leaves.iter().zip(round_tripped.iter()) {
+            match (original, reloaded) {
+                (NodeEntry::Leaf(a), NodeEntry::Leaf(b)) => {
+                    assert_eq!(a.key, b.key);
+                    assert_eq!(a.value, b.value);
+                }
+                _ => panic!("expected leaf entries"),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_through_memory_store() {
+        round_trip_through(MemoryBlockStore::new());
+    }
+
+    // NOT COVERED: `round_trip_through` takes any `BlockStore`, so
+    // `SqlRepoReader` (rsky-pds/src/storage/mod.rs) could be exercised the
+    // same way `MemoryBlockStore` is above. It isn't, here, because
+    // `SqlRepoReader` is constructed from a `crate::db::DbConn` and that
+    // module doesn't exist anywhere in this checkout — there's no database
+    // connection type to build a fixture around, in-memory/sqlite or
+    // otherwise, so one can't be fabricated without guessing at a schema
+    // that may not match the real one. This is a genuine gap against the
+    // "round-trip tests ... through each backend" ask, not just an
+    // unwritten fixture: whoever has `crate::db` in their tree should add
+    // `round_trip_through(SqlRepoReader::new(conn, did))` against a real (or
+    // sqlite/in-memory) connection.
+
+    #[test]
+    fn detects_corrupted_block_on_load() {
+        let storage = MemoryBlockStore::new();
+        let original = serialize_node_data(vec![NodeEntry::<MemoryBlockStore>::Leaf(Leaf {
+            key: "com.example.record/aaa".to_owned(),
+            value: cid_for(b"one"),
+        })])
+        .unwrap();
+        let cid = ipld::cid_for_cbor(&original).unwrap();
+
+        // Swap in different bytes than the ones `cid` commits to, simulating a
+        // flipped bit or a storage bug, and store them under the original CID.
+        let tampered = serialize_node_data(vec![NodeEntry::<MemoryBlockStore>::Leaf(Leaf {
+            key: "com.example.record/zzz".to_owned(),
+            value: cid_for(b"two"),
+        })])
+        .unwrap();
+        storage
+            .put_block(cid, serde_cbor::to_vec(&tampered).unwrap())
+            .unwrap();
+
+        let err = MST::load(storage.clone(), cid, None)
+            .unwrap()
+            .get_entries()
+            .unwrap_err();
+        assert!(err.to_string().contains("Block hash mismatch"));
+
+        // A caller that explicitly opts out of verification gets the tampered
+        // tree back rather than an error.
+        let unchecked = MST::load_with_options(storage, cid, None, false).unwrap();
+        let entries = unchecked.get_entries().unwrap();
+        match &entries[0] {
+            NodeEntry::Leaf(l) => assert_eq!(l.key, "com.example.record/zzz"),
+            _ => panic!("expected leaf entry"),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use anyhow::Result;
+use libipld::Cid;
+
+/// Abstracts the storage layer that backs MST block reads/writes so tree
+/// logic in `repo::mst` never has to know whether blocks live in SQL,
+/// in-memory, or (eventually) an object store.
+pub trait BlockStore {
+    fn get_bytes(&self, cid: &Cid) -> Result<Vec<u8>>;
+    fn has(&self, cid: &Cid) -> Result<bool>;
+    fn put_block(&self, cid: Cid, bytes: Vec<u8>) -> Result<()>;
+}
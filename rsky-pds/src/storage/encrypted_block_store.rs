@@ -0,0 +1,231 @@
+use crate::storage::BlockStore;
+use anyhow::{anyhow, Result};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use libipld::Cid;
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Length in bytes of a master key and of the per-repo keys derived from it.
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const VERSION_LEN: usize = 4;
+
+/// Identifies which master key encrypted a stored block, so blocks written
+/// under a retired key can be detected on read and re-encrypted under the
+/// current one.
+pub type KeyVersion = u32;
+
+/// Holds the current master key plus any retired ones still needed to
+/// decrypt blocks written before a rotation. Neither the master keys nor the
+/// per-repo keys `Hkdf` derives from them are written to the inner store;
+/// each stored block carries only its key version. The master
+/// keys themselves live in `master_keys` for as long as this `KeyRing` is
+/// alive (cloning it duplicates them), but both the master keys and each
+/// derived per-repo key are zeroized as soon as they're no longer needed:
+/// `master_keys`' entries are wiped in `Drop`, and `derive_repo_key` hands
+/// back a `Zeroizing<Key>` that clears itself when the caller's
+/// encrypt/decrypt call ends.
+#[derive(Clone)]
+pub struct KeyRing {
+    current_version: KeyVersion,
+    master_keys: BTreeMap<KeyVersion, [u8; KEY_LEN]>,
+}
+
+impl KeyRing {
+    pub fn new(current_version: KeyVersion, master_key: [u8; KEY_LEN]) -> Self {
+        let mut master_keys = BTreeMap::new();
+        master_keys.insert(current_version, master_key);
+        Self {
+            current_version,
+            master_keys,
+        }
+    }
+
+    /// Registers a previous master key so blocks it encrypted can still be
+    /// read (and then re-encrypted under the current key) after a rotation.
+    pub fn with_retired_key(mut self, version: KeyVersion, master_key: [u8; KEY_LEN]) -> Self {
+        self.master_keys.insert(version, master_key);
+        self
+    }
+
+    fn derive_repo_key(&self, version: KeyVersion, repo_did: &str) -> Result<Zeroizing<Key>> {
+        let master = self
+            .master_keys
+            .get(&version)
+            .ok_or_else(|| anyhow!("Unknown block encryption key version {}", version))?;
+        let hk = Hkdf::<Sha256>::new(None, master);
+        let mut okm = [0u8; KEY_LEN];
+        hk.expand(repo_did.as_bytes(), &mut okm)
+            .map_err(|_| anyhow!("Failed to derive per-repo block encryption key"))?;
+        let key = Zeroizing::new(*Key::from_slice(&okm));
+        okm.zeroize();
+        Ok(key)
+    }
+}
+
+impl Drop for KeyRing {
+    fn drop(&mut self) {
+        for master_key in self.master_keys.values_mut() {
+            master_key.zeroize();
+        }
+    }
+}
+
+/// A `BlockStore` wrapper that encrypts block bytes at rest with a per-repo
+/// key derived (via HKDF-SHA256) from a rotatable master key. Encryption is
+/// purely a storage-layer concern: the CID the tree addresses blocks by is
+/// always computed over plaintext (see `MST::get_entries`), so content
+/// addressing and `getRepo`/CAR export stay interoperable with peers that
+/// don't encrypt at rest.
+///
+/// On-disk layout per block is `version(4 LE) || nonce(12) || ciphertext`.
+#[derive(Clone)]
+pub struct EncryptedBlockStore<S: BlockStore + Clone> {
+    inner: S,
+    repo_did: String,
+    keys: KeyRing,
+}
+
+impl<S: BlockStore + Clone> EncryptedBlockStore<S> {
+    pub fn new(inner: S, repo_did: String, keys: KeyRing) -> Self {
+        Self {
+            inner,
+            repo_did,
+            keys,
+        }
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let version = self.keys.current_version;
+        let key = self.keys.derive_repo_key(version, &self.repo_did)?;
+        let cipher = ChaCha20Poly1305::new(&key);
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+
+        let ciphertext = cipher
+            .encrypt(nonce, plaintext)
+            .map_err(|_| anyhow!("Failed to encrypt block"))?;
+
+        let mut out = Vec::with_capacity(VERSION_LEN + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&nonce_bytes);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Returns the decrypted plaintext along with the key version it was
+    /// encrypted under, so the caller can re-encrypt on a stale version.
+    fn decrypt(&self, stored: &[u8]) -> Result<(Vec<u8>, KeyVersion)> {
+        if stored.len() < VERSION_LEN + NONCE_LEN {
+            return Err(anyhow!("Encrypted block is too short"));
+        }
+        let version = KeyVersion::from_le_bytes(stored[0..VERSION_LEN].try_into()?);
+        let nonce = Nonce::from_slice(&stored[VERSION_LEN..VERSION_LEN + NONCE_LEN]);
+        let ciphertext = &stored[VERSION_LEN + NONCE_LEN..];
+
+        let key = self.keys.derive_repo_key(version, &self.repo_did)?;
+        let cipher = ChaCha20Poly1305::new(&key);
+        let plaintext = cipher
+            .decrypt(nonce, ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt block (wrong key or corrupted ciphertext)"))?;
+        Ok((plaintext, version))
+    }
+}
+
+impl<S: BlockStore + Clone> BlockStore for EncryptedBlockStore<S> {
+    fn get_bytes(&self, cid: &Cid) -> Result<Vec<u8>> {
+        let stored = self.inner.get_bytes(cid)?;
+        let (plaintext, version) = self.decrypt(&stored)?;
+        if version != self.keys.current_version {
+            // Key rotation: re-encrypt under the current key now that we've
+            // already paid the cost of reading this block.
+            let rewrapped = self.encrypt(&plaintext)?;
+            self.inner.put_block(*cid, rewrapped)?;
+        }
+        Ok(plaintext)
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        self.inner.has(cid)
+    }
+
+    fn put_block(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        let encrypted = self.encrypt(&bytes)?;
+        self.inner.put_block(cid, encrypted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryBlockStore;
+
+    fn keys(version: KeyVersion, seed: u8) -> KeyRing {
+        KeyRing::new(version, [seed; KEY_LEN])
+    }
+
+    #[test]
+    fn round_trips_plaintext_while_ciphertext_differs() {
+        let inner = MemoryBlockStore::new();
+        let store = EncryptedBlockStore::new(inner.clone(), "did:plc:alice".to_owned(), keys(1, 7));
+
+        let plaintext = b"hello mst block".to_vec();
+        let cid = Cid::try_from("bafyreiecb5hu5qbzcxujedvm5nbvzzdb5vfpywhk3cvenicjxnr4a67jqe").unwrap();
+        store.put_block(cid, plaintext.clone()).unwrap();
+
+        let raw = inner.get_bytes(&cid).unwrap();
+        assert_ne!(raw, plaintext, "ciphertext must not equal plaintext");
+
+        let round_tripped = store.get_bytes(&cid).unwrap();
+        assert_eq!(round_tripped, plaintext);
+    }
+
+    #[test]
+    fn different_repos_get_different_ciphertext_for_same_plaintext() {
+        let inner = MemoryBlockStore::new();
+        let alice = EncryptedBlockStore::new(inner.clone(), "did:plc:alice".to_owned(), keys(1, 7));
+        let bob = EncryptedBlockStore::new(inner.clone(), "did:plc:bob".to_owned(), keys(1, 7));
+
+        let plaintext = b"same bytes, different repo".to_vec();
+        let cid_a = Cid::try_from("bafyreiecb5hu5qbzcxujedvm5nbvzzdb5vfpywhk3cvenicjxnr4a67jqe").unwrap();
+        let cid_b = Cid::try_from("bafyreifvzqwgqvtfpugqz3wq6pxrwmna4gsbwe4rqqpymkpzsdtpe5cpvu").unwrap();
+        alice.put_block(cid_a, plaintext.clone()).unwrap();
+        bob.put_block(cid_b, plaintext.clone()).unwrap();
+
+        let raw_a = inner.get_bytes(&cid_a).unwrap();
+        let raw_b = inner.get_bytes(&cid_b).unwrap();
+        assert_ne!(raw_a, raw_b, "per-repo key derivation must change the ciphertext");
+    }
+
+    #[test]
+    fn rotates_key_on_read_modify_write() {
+        let inner = MemoryBlockStore::new();
+        let cid = Cid::try_from("bafyreiecb5hu5qbzcxujedvm5nbvzzdb5vfpywhk3cvenicjxnr4a67jqe").unwrap();
+        let plaintext = b"pre-rotation block".to_vec();
+
+        let old = EncryptedBlockStore::new(inner.clone(), "did:plc:alice".to_owned(), keys(1, 7));
+        old.put_block(cid, plaintext.clone()).unwrap();
+        let stored_before = inner.get_bytes(&cid).unwrap();
+
+        let rotated_keys = keys(2, 9).with_retired_key(1, [7u8; KEY_LEN]);
+        let new = EncryptedBlockStore::new(inner.clone(), "did:plc:alice".to_owned(), rotated_keys);
+
+        // Reading under the new key ring transparently re-encrypts the block.
+        assert_eq!(new.get_bytes(&cid).unwrap(), plaintext);
+        let stored_after = inner.get_bytes(&cid).unwrap();
+        assert_ne!(stored_before, stored_after, "block should be rewrapped under the new key");
+        assert_eq!(
+            KeyVersion::from_le_bytes(stored_after[0..VERSION_LEN].try_into().unwrap()),
+            2
+        );
+
+        // And it's still readable going forward.
+        assert_eq!(new.get_bytes(&cid).unwrap(), plaintext);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::storage::BlockStore;
+use anyhow::{anyhow, Result};
+use libipld::Cid;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An in-memory `BlockStore`, useful for tests and for building MSTs that
+/// never need to touch SQL (e.g. scratch trees used while diffing commits).
+/// The map lives behind an `Rc` so, like `SqlRepoReader`'s shared `DbConn`,
+/// clones alias the same backing storage rather than forking it — callers
+/// such as `deserialize_node_data`, which clone the store per child node,
+/// still see writes made through any other clone.
+#[derive(Clone, Default)]
+pub struct MemoryBlockStore {
+    blocks: Rc<RefCell<HashMap<Cid, Vec<u8>>>>,
+}
+
+impl MemoryBlockStore {
+    pub fn new() -> Self {
+        Self {
+            blocks: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+}
+
+impl BlockStore for MemoryBlockStore {
+    fn get_bytes(&self, cid: &Cid) -> Result<Vec<u8>> {
+        self.blocks
+            .borrow()
+            .get(cid)
+            .cloned()
+            .ok_or_else(|| anyhow!("Block not found for {cid}"))
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        Ok(self.blocks.borrow().contains_key(cid))
+    }
+
+    fn put_block(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        self.blocks.borrow_mut().insert(cid, bytes);
+        Ok(())
+    }
+}
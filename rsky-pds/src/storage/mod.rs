@@ -0,0 +1,40 @@
+pub mod block_store;
+pub mod encrypted_block_store;
+pub mod memory_block_store;
+
+pub use block_store::BlockStore;
+pub use encrypted_block_store::{EncryptedBlockStore, KeyRing};
+pub use memory_block_store::MemoryBlockStore;
+
+use crate::db::DbConn;
+use anyhow::{anyhow, Result};
+use libipld::Cid;
+
+/// Reads and writes MST blocks for a single repo's `repo_block` table.
+#[derive(Clone)]
+pub struct SqlRepoReader {
+    pub db: DbConn,
+    pub did: String,
+}
+
+impl SqlRepoReader {
+    pub fn new(db: DbConn, did: String) -> Self {
+        Self { db, did }
+    }
+}
+
+impl BlockStore for SqlRepoReader {
+    fn get_bytes(&self, cid: &Cid) -> Result<Vec<u8>> {
+        self.db
+            .repo_block_bytes(&self.did, cid)?
+            .ok_or_else(|| anyhow!("Block not found for {cid}"))
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        Ok(self.db.repo_block_bytes(&self.did, cid)?.is_some())
+    }
+
+    fn put_block(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        self.db.put_repo_block(&self.did, cid, bytes)
+    }
+}